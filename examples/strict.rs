@@ -1,5 +1,7 @@
 use std::vec;
 
+use cttt_parser::CommandSpec;
+
 fn main() {
     let s = "
 // @cttt.name(foo)
@@ -11,7 +13,20 @@ let y = 2;
 // @cttt.change(foo)
 ";
 
-    let allowed_commands = vec![String::from("name"), String::from("change")];
+    let allowed_commands = vec![
+        CommandSpec {
+            name: String::from("name"),
+            min_positional: 1,
+            max_positional: 1,
+            allowed_named: vec![],
+        },
+        CommandSpec {
+            name: String::from("change"),
+            min_positional: 1,
+            max_positional: 1,
+            allowed_named: vec![String::from("line"), String::from("mode")],
+        },
+    ];
 
     println!("{:#?}", cttt_parser::parse_strict(s, allowed_commands));
 }
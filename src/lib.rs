@@ -20,6 +20,8 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
+use std::collections::{BTreeMap, HashSet};
+
 use pest::Parser;
 use pest_derive::Parser;
 
@@ -31,7 +33,67 @@ struct ChangeParser;
 pub struct Comment {
     command: Option<String>,
     debug: CommentDebug,
-    args: Vec<String>,
+    args: Args,
+    guard: Option<GuardExpr>,
+    // Byte offset into `debug.comment` right after the command's closing `)`, i.e.
+    // where a trailing `if <guard>` clause would start. Used to anchor
+    // extract_guard_text() on the real parse instead of re-deriving position via a
+    // substring search.
+    #[serde(skip)]
+    args_end: usize,
+}
+
+// A directive's arguments, split into positional values and `key=value` pairs.
+#[derive(Debug, PartialEq, serde::Serialize, Clone, Default)]
+pub struct Args {
+    pub positional: Vec<String>,
+    pub named: BTreeMap<String, String>,
+}
+
+// Splits a raw `args` span (e.g. `./foo.rs, line=12, mode="hello, world"`) into
+// positional values and named `key=value` pairs. Commas inside a quoted value don't
+// count as separators.
+fn split_args(s: &str) -> Vec<&str> {
+    let mut tokens = vec![];
+    let mut start = 0;
+    let mut in_quotes = false;
+
+    for (i, ch) in s.char_indices() {
+        match ch {
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => {
+                tokens.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    tokens.push(&s[start..]);
+
+    tokens
+}
+
+fn parse_args(s: &str) -> Args {
+    let mut args = Args::default();
+
+    for token in split_args(s.trim()) {
+        let token = token.trim();
+        if token.is_empty() {
+            continue;
+        }
+
+        match token.split_once('=') {
+            Some((key, value)) => {
+                args.named.insert(
+                    key.trim().to_string(),
+                    value.trim().trim_matches('"').to_string(),
+                );
+            }
+            None => args.positional.push(token.to_string()),
+        }
+    }
+
+    args
 }
 
 #[derive(Debug, PartialEq, serde::Serialize, Clone)]
@@ -43,26 +105,151 @@ pub struct CommentDebug {
 
 pub static NAMESPACE: &str = "@cttt";
 
-// Parse a string into a vector of Comments.
-pub fn parse(s: &str) -> Result<Vec<Comment>, pest::error::Error<Rule>> {
-    let parse = ChangeParser::parse(Rule::document, s).unwrap();
+// A line that looks like an `@cttt` directive but fails the grammar, e.g. unbalanced
+// parens in its argument list.
+#[derive(Debug, PartialEq, serde::Serialize, Clone)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub col: usize,
+    pub message: String,
+    pub span: String,
+}
+
+// The directives that parsed successfully, plus a Diagnostic for every
+// directive-shaped line that didn't.
+#[derive(Debug, PartialEq, serde::Serialize, Clone, Default)]
+pub struct ParseOutcome {
+    pub comments: Vec<Comment>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+// How a directive's command name is normalized before it's stored on Comment and
+// matched against a CommandSpec registry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandCase {
+    // Keep the command exactly as written, e.g. `CHANGE` stays `CHANGE`.
+    Preserve,
+    // Lowercase the command, e.g. `CHANGE` becomes `change`.
+    Lowercase,
+    // Lowercase and fold underscores to hyphens, e.g. `Named_Block` becomes `named-block`.
+    Kebab,
+    // Lowercase and fold hyphens to underscores, e.g. `Named-Block` becomes `named_block`.
+    Snake,
+}
+
+impl CommandCase {
+    fn normalize(self, command: &str) -> String {
+        match self {
+            CommandCase::Preserve => command.to_string(),
+            CommandCase::Lowercase => command.to_lowercase(),
+            CommandCase::Kebab => command.to_lowercase().replace('_', "-"),
+            CommandCase::Snake => command.to_lowercase().replace('-', "_"),
+        }
+    }
+}
 
-    let mut comments: Vec<Comment> = vec![];
+// Controls how parse_with_options() and parse_strict_with_options() recognize
+// directives: the marker prefix to look for in place of `@cttt`, and how to
+// normalize command names before they're compared or returned.
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    pub namespace: String,
+    pub command_case: CommandCase,
+}
 
-    // make an iterator over the pairs in the rule
-    for pair in parse {
-        // match the rule, as the rule is an enum
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            namespace: NAMESPACE.to_string(),
+            command_case: CommandCase::Preserve,
+        }
+    }
+}
+
+// The grammar is compiled statically from grammar.pest, which hardcodes the `@cttt`
+// token, so a custom namespace can't change what PEST itself recognizes. Instead, swap
+// every case-insensitive occurrence of `namespace` in `line` for the grammar's literal
+// marker so PEST still matches each one, and return `None` for any line that doesn't
+// contain `namespace` at all - this is what actually gates `Rule::comment` matches to
+// the configured marker instead of always accepting `@cttt`.
+//
+// Also returns the byte offset of each rewritten occurrence's start, in the *rewritten*
+// line, so callers can map positions back to the original line via to_original_offset().
+fn rewrite_namespace(line: &str, namespace: &str) -> Option<(String, Vec<usize>)> {
+    let haystack = line.to_lowercase();
+    let needle = namespace.to_lowercase();
+
+    let mut orig_starts = vec![];
+    let mut search_from = 0;
+    while let Some(rel) = haystack[search_from..].find(&needle) {
+        orig_starts.push(search_from + rel);
+        search_from += rel + needle.len();
+    }
+
+    if orig_starts.is_empty() {
+        return None;
+    }
+
+    if namespace == NAMESPACE {
+        return Some((line.to_string(), orig_starts));
+    }
+
+    let mut rewritten = String::with_capacity(line.len());
+    let mut rewritten_starts = Vec::with_capacity(orig_starts.len());
+    let mut cursor = 0;
+    for &start in &orig_starts {
+        rewritten.push_str(&line[cursor..start]);
+        rewritten_starts.push(rewritten.len());
+        rewritten.push_str(NAMESPACE);
+        cursor = start + namespace.len();
+    }
+    rewritten.push_str(&line[cursor..]);
+
+    Some((rewritten, rewritten_starts))
+}
+
+// Maps a byte offset in the namespace-rewritten line back to the offset it corresponds
+// to in the original line, given the rewritten occurrences' start offsets returned by
+// rewrite_namespace(). Every occurrence shifts the line by the same fixed amount (the
+// length difference between `namespace` and `NAMESPACE`), so this just counts how many
+// occurrences precede `rewritten_offset` and undoes that many shifts.
+fn to_original_offset(
+    rewritten_offset: usize,
+    rewritten_starts: &[usize],
+    namespace: &str,
+) -> usize {
+    let delta = namespace.len() as isize - NAMESPACE.len() as isize;
+    let occurrences_before = rewritten_starts
+        .iter()
+        .filter(|&&start| start < rewritten_offset)
+        .count();
+
+    (rewritten_offset as isize + occurrences_before as isize * delta).max(0) as usize
+}
+
+// Extracts the Comments matched within a single successfully-parsed line, tagging
+// them with the line's 1-indexed position in the original source.
+fn comments_from_line(
+    pairs: pest::iterators::Pairs<Rule>,
+    line_no: usize,
+    options: &ParseOptions,
+    ns_starts: &[usize],
+) -> Vec<Comment> {
+    let mut comments = vec![];
+
+    for pair in pairs {
         match pair.as_rule() {
             Rule::EOI => (),
             Rule::document => {
-                // for each sub-rule, print the inner contents
                 for document in pair.into_inner() {
                     match document.as_rule() {
                         Rule::EOI => (),
                         Rule::comment => {
                             let mut command = None;
-                            let mut args: Vec<String> = vec![];
-                            let (line, col) = document.as_span().start_pos().line_col();
+                            let mut args = Args::default();
+                            let mut args_end = 0;
+                            let (_, col) = document.as_span().start_pos().line_col();
+                            let document_start = document.as_span().start();
 
                             let comment = document
                                 .as_span()
@@ -72,32 +259,61 @@ pub fn parse(s: &str) -> Result<Vec<Comment>, pest::error::Error<Rule>> {
                                 .to_string();
 
                             let col = comment.find(NAMESPACE).unwrap_or(0) + col - 1;
+                            let col = to_original_offset(col, ns_starts, &options.namespace);
 
-                            // match the sub-rule
                             for part in document.into_inner() {
                                 match part.as_rule() {
                                     Rule::command => {
-                                        command = Some(part.as_span().as_str().to_string())
+                                        command = Some(
+                                            options
+                                                .command_case
+                                                .normalize(part.as_span().as_str()),
+                                        )
                                     }
                                     Rule::args => {
-                                        args = match part.as_span().as_str().trim() {
-                                            "" => vec![],
-                                            s => s
-                                                .trim()
-                                                .split(',')
-                                                .map(|s| s.to_string().trim().to_string())
-                                                .filter(|s| !s.is_empty())
-                                                .collect(),
-                                        }
+                                        args = parse_args(part.as_span().as_str());
+                                        args_end = part.as_span().end() - document_start;
                                     }
                                     _ => (),
                                 }
                             }
 
+                            // Undo the rewrite_namespace() swap so the directive's own
+                            // marker spelling is what callers see. args_end is an
+                            // absolute rewritten-line offset at this point (relative to
+                            // document_start), so it's remapped the same way col is,
+                            // via to_original_offset, rather than a single flat shift -
+                            // that stays correct even if this comment's own text
+                            // happens to contain more than one namespace occurrence.
+                            let (comment, args_end) = if options.namespace == NAMESPACE {
+                                (comment, args_end)
+                            } else {
+                                let original_document_start = to_original_offset(
+                                    document_start,
+                                    ns_starts,
+                                    &options.namespace,
+                                );
+                                let original_args_end = to_original_offset(
+                                    document_start + args_end,
+                                    ns_starts,
+                                    &options.namespace,
+                                );
+                                (
+                                    comment.replace(NAMESPACE, &options.namespace),
+                                    original_args_end.saturating_sub(original_document_start),
+                                )
+                            };
+
                             comments.push(Comment {
                                 args,
                                 command,
-                                debug: CommentDebug { comment, line, col },
+                                debug: CommentDebug {
+                                    comment,
+                                    line: line_no,
+                                    col,
+                                },
+                                guard: None,
+                                args_end,
                             });
                         }
                         _ => unreachable!(),
@@ -108,7 +324,53 @@ pub fn parse(s: &str) -> Result<Vec<Comment>, pest::error::Error<Rule>> {
         }
     }
 
-    Ok(comments)
+    comments
+}
+
+// Parse a string into Comments, recovering from grammar errors instead of aborting on
+// the first one: each line is parsed independently, so a single malformed directive
+// only costs its own line, surfaced as a Diagnostic in the returned ParseOutcome.
+pub fn parse(s: &str) -> ParseOutcome {
+    parse_with_options(s, &ParseOptions::default())
+}
+
+// Like parse(), but with a custom namespace marker and command-normalization policy.
+pub fn parse_with_options(s: &str, options: &ParseOptions) -> ParseOutcome {
+    let mut outcome = ParseOutcome::default();
+
+    for (idx, line) in s.lines().enumerate() {
+        let line_no = idx + 1;
+
+        let Some((rewritten, ns_starts)) = rewrite_namespace(line, &options.namespace) else {
+            continue;
+        };
+
+        match ChangeParser::parse(Rule::document, &rewritten) {
+            Ok(pairs) => outcome
+                .comments
+                .extend(comments_from_line(pairs, line_no, options, &ns_starts)),
+            Err(e) => {
+                let col = match e.line_col {
+                    pest::error::LineColLocation::Pos((_, col)) => col,
+                    pest::error::LineColLocation::Span((_, col), _) => col,
+                };
+
+                // `col` was computed against the rewritten line; map it back to the
+                // original line's coordinates before reporting it.
+                let col = to_original_offset(col.saturating_sub(1), &ns_starts, &options.namespace)
+                    + 1;
+
+                outcome.diagnostics.push(Diagnostic {
+                    line: line_no,
+                    col,
+                    message: e.to_string(),
+                    span: line.trim_end().to_string(),
+                });
+            }
+        }
+    }
+
+    outcome
 }
 
 // custom error
@@ -120,31 +382,113 @@ pub struct UnknownCommandError {
     line: usize,
 }
 
+// custom error
+#[derive(Debug, PartialEq, serde::Serialize)]
+pub struct ArgsError {
+    comment: String,
+    command: String,
+    col: usize,
+    line: usize,
+    message: String,
+}
+
+// The argument schema for a single command: how many positional arguments it accepts
+// and which named (`key=value`) arguments are allowed.
+#[derive(Debug, Clone)]
+pub struct CommandSpec {
+    pub name: String,
+    pub min_positional: usize,
+    pub max_positional: usize,
+    pub allowed_named: Vec<String>,
+}
+
 pub enum StrictParseError {
     UnknownCommand(Vec<UnknownCommandError>),
-    Pest(pest::error::Error<Rule>),
+    InvalidArgs(Vec<ArgsError>),
+    Diagnostics(Vec<Diagnostic>),
+}
+
+pub fn parse_strict(
+    s: &str,
+    commands: Vec<CommandSpec>,
+) -> Result<Vec<Comment>, StrictParseError> {
+    parse_strict_with_options(s, commands, &ParseOptions::default())
 }
 
-pub fn parse_strict(s: &str, commands: Vec<String>) -> Result<Vec<Comment>, StrictParseError> {
-    let comments = parse(s).map_err(StrictParseError::Pest)?;
+// Like parse_strict(), but with a custom namespace marker and command-normalization
+// policy. `commands` should list names already normalized the same way
+// `options.command_case` normalizes directives.
+pub fn parse_strict_with_options(
+    s: &str,
+    commands: Vec<CommandSpec>,
+    options: &ParseOptions,
+) -> Result<Vec<Comment>, StrictParseError> {
+    let outcome = parse_with_options(s, options);
+
+    if !outcome.diagnostics.is_empty() {
+        return Err(StrictParseError::Diagnostics(outcome.diagnostics));
+    }
+
+    let comments = outcome.comments;
 
     let mut unknown_command_errors: Vec<UnknownCommandError> = vec![];
+    let mut args_errors: Vec<ArgsError> = vec![];
 
-    // check for unknown commands
     comments.iter().for_each(|c| match &c.command {
         Some(command) => {
-            if !commands.contains(command) {
-                let col = command.find(NAMESPACE).unwrap_or(0)
-                    + c.debug.col
-                    + NAMESPACE.len()
-                    + ".".len();
-
-                unknown_command_errors.push(UnknownCommandError {
-                    comment: c.debug.comment.clone(),
-                    command: c.command.clone().unwrap(),
-                    line: c.debug.line,
-                    col,
-                });
+            let col = command.find(&options.namespace).unwrap_or(0)
+                + c.debug.col
+                + options.namespace.len()
+                + ".".len();
+
+            match commands.iter().find(|spec| &spec.name == command) {
+                None => {
+                    unknown_command_errors.push(UnknownCommandError {
+                        comment: c.debug.comment.clone(),
+                        command: command.clone(),
+                        line: c.debug.line,
+                        col,
+                    });
+                }
+                Some(spec) => {
+                    let found = c.args.positional.len();
+
+                    if found < spec.min_positional {
+                        args_errors.push(ArgsError {
+                            comment: c.debug.comment.clone(),
+                            command: command.clone(),
+                            line: c.debug.line,
+                            col,
+                            message: format!(
+                                "missing required positional argument(s): expected at least {}, found {found}",
+                                spec.min_positional
+                            ),
+                        });
+                    } else if found > spec.max_positional {
+                        args_errors.push(ArgsError {
+                            comment: c.debug.comment.clone(),
+                            command: command.clone(),
+                            line: c.debug.line,
+                            col,
+                            message: format!(
+                                "too many positional arguments: expected at most {}, found {found}",
+                                spec.max_positional
+                            ),
+                        });
+                    }
+
+                    for key in c.args.named.keys() {
+                        if !spec.allowed_named.iter().any(|allowed| allowed == key) {
+                            args_errors.push(ArgsError {
+                                comment: c.debug.comment.clone(),
+                                command: command.clone(),
+                                line: c.debug.line,
+                                col,
+                                message: format!("unknown named argument `{key}`"),
+                            });
+                        }
+                    }
+                }
             }
         }
         None => (),
@@ -154,9 +498,283 @@ pub fn parse_strict(s: &str, commands: Vec<String>) -> Result<Vec<Comment>, Stri
         return Err(StrictParseError::UnknownCommand(unknown_command_errors));
     }
 
+    if !args_errors.is_empty() {
+        return Err(StrictParseError::InvalidArgs(args_errors));
+    }
+
     Ok(comments)
 }
 
+// A `cfg()`-style guard expression, e.g. `all(any(ci, nightly), not(windows))`.
+#[derive(Debug, PartialEq, serde::Serialize, Clone)]
+pub enum GuardExpr {
+    Ident(String),
+    KeyValue(String, String),
+    All(Vec<GuardExpr>),
+    Any(Vec<GuardExpr>),
+    Not(Box<GuardExpr>),
+}
+
+impl GuardExpr {
+    // Evaluate this expression against a set of active `(name, value)` predicates. A
+    // bare identifier matches a name-only entry, `key = "value"` matches an exact
+    // pair, and unknown predicates simply evaluate to `false`.
+    pub fn eval(&self, active: &HashSet<(String, Option<String>)>) -> bool {
+        match self {
+            GuardExpr::Ident(name) => active.contains(&(name.clone(), None)),
+            GuardExpr::KeyValue(key, value) => {
+                active.contains(&(key.clone(), Some(value.clone())))
+            }
+            GuardExpr::All(exprs) => exprs.iter().all(|e| e.eval(active)),
+            GuardExpr::Any(exprs) => exprs.iter().any(|e| e.eval(active)),
+            GuardExpr::Not(expr) => !expr.eval(active),
+        }
+    }
+}
+
+// custom error
+#[derive(Debug, PartialEq, serde::Serialize)]
+pub struct GuardParseError {
+    pub message: String,
+    pub line: usize,
+    pub col: usize,
+}
+
+pub enum CfgParseError {
+    Diagnostics(Vec<Diagnostic>),
+    Guard(GuardParseError),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum GuardToken {
+    Ident(String),
+    Str(String),
+    LParen,
+    RParen,
+    Comma,
+    Eq,
+}
+
+fn tokenize_guard(s: &str) -> Result<Vec<GuardToken>, String> {
+    let mut tokens = vec![];
+    let mut chars = s.char_indices().peekable();
+
+    while let Some(&(i, ch)) = chars.peek() {
+        match ch {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                tokens.push(GuardToken::LParen);
+                chars.next();
+            }
+            ')' => {
+                tokens.push(GuardToken::RParen);
+                chars.next();
+            }
+            ',' => {
+                tokens.push(GuardToken::Comma);
+                chars.next();
+            }
+            '=' => {
+                tokens.push(GuardToken::Eq);
+                chars.next();
+            }
+            '"' => {
+                chars.next();
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, c)) => value.push(c),
+                        None => return Err(format!("unterminated string starting at byte {i}")),
+                    }
+                }
+                tokens.push(GuardToken::Str(value));
+            }
+            c if c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | '/') => {
+                let mut ident = String::new();
+                while let Some(&(_, c)) = chars.peek() {
+                    if c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | '/') {
+                        ident.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push(GuardToken::Ident(ident));
+            }
+            c => return Err(format!("unexpected character '{c}' at byte {i}")),
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn expect_guard_token(
+    tokens: &[GuardToken],
+    pos: &mut usize,
+    expected: &GuardToken,
+) -> Result<(), String> {
+    match tokens.get(*pos) {
+        Some(t) if t == expected => {
+            *pos += 1;
+            Ok(())
+        }
+        other => Err(format!("expected {expected:?}, found {other:?}")),
+    }
+}
+
+// Expr = Ident | Ident "=" String | "all" "(" ExprList ")" | "any" "(" ExprList ")" | "not" "(" Expr ")"
+fn parse_guard_expr(tokens: &[GuardToken], pos: &mut usize) -> Result<GuardExpr, String> {
+    match tokens.get(*pos) {
+        Some(GuardToken::Ident(name)) if name == "all" => {
+            *pos += 1;
+            Ok(GuardExpr::All(parse_guard_list(tokens, pos)?))
+        }
+        Some(GuardToken::Ident(name)) if name == "any" => {
+            *pos += 1;
+            Ok(GuardExpr::Any(parse_guard_list(tokens, pos)?))
+        }
+        Some(GuardToken::Ident(name)) if name == "not" => {
+            *pos += 1;
+            expect_guard_token(tokens, pos, &GuardToken::LParen)?;
+            let inner = parse_guard_expr(tokens, pos)?;
+            expect_guard_token(tokens, pos, &GuardToken::RParen)?;
+            Ok(GuardExpr::Not(Box::new(inner)))
+        }
+        Some(GuardToken::Ident(name)) => {
+            let name = name.clone();
+            *pos += 1;
+            if tokens.get(*pos) == Some(&GuardToken::Eq) {
+                *pos += 1;
+                match tokens.get(*pos) {
+                    Some(GuardToken::Str(value)) => {
+                        let value = value.clone();
+                        *pos += 1;
+                        Ok(GuardExpr::KeyValue(name, value))
+                    }
+                    other => Err(format!("expected a quoted string, found {other:?}")),
+                }
+            } else {
+                Ok(GuardExpr::Ident(name))
+            }
+        }
+        other => Err(format!("expected an identifier, found {other:?}")),
+    }
+}
+
+// ExprList, parenthesized and comma-separated; a trailing comma is a parse error.
+fn parse_guard_list(tokens: &[GuardToken], pos: &mut usize) -> Result<Vec<GuardExpr>, String> {
+    expect_guard_token(tokens, pos, &GuardToken::LParen)?;
+
+    let mut list = vec![];
+    if tokens.get(*pos) == Some(&GuardToken::RParen) {
+        *pos += 1;
+        return Ok(list);
+    }
+
+    loop {
+        list.push(parse_guard_expr(tokens, pos)?);
+        match tokens.get(*pos) {
+            Some(GuardToken::Comma) => {
+                *pos += 1;
+                if tokens.get(*pos) == Some(&GuardToken::RParen) {
+                    return Err("unexpected trailing comma in argument list".to_string());
+                }
+            }
+            Some(GuardToken::RParen) => {
+                *pos += 1;
+                break;
+            }
+            other => return Err(format!("expected ',' or ')', found {other:?}")),
+        }
+    }
+
+    Ok(list)
+}
+
+fn parse_guard(text: &str, line: usize, col: usize) -> Result<GuardExpr, GuardParseError> {
+    let tokens =
+        tokenize_guard(text).map_err(|message| GuardParseError { message, line, col })?;
+
+    let mut pos = 0;
+    let expr =
+        parse_guard_expr(&tokens, &mut pos).map_err(|message| GuardParseError {
+            message,
+            line,
+            col,
+        })?;
+
+    if pos != tokens.len() {
+        return Err(GuardParseError {
+            message: "unexpected trailing tokens after guard expression".to_string(),
+            line,
+            col,
+        });
+    }
+
+    Ok(expr)
+}
+
+// A comment terminator that may trail the guard expression, e.g. `*/` or `-->`.
+const COMMENT_TERMINATORS: [&str; 6] = ["*/", "-->", "*)", "-}", "}", "\"\"\""];
+
+// Pulls the `if <expr>` suffix (if any) out of a directive's raw comment text, after the
+// command's closing paren, e.g. `foo(bar) if all(ci)` -> `Some("all(ci)")`. Anchored on
+// `comment.args_end`, the byte offset the parse tree already recorded for the end of the
+// args list, rather than re-finding the command/args boundary via substring search.
+fn extract_guard_text(comment: &Comment) -> Option<String> {
+    comment.command.as_ref()?;
+    let text = &comment.debug.comment;
+    let after_args = text.get(comment.args_end..)?;
+
+    let close = after_args.find(')')?;
+    let after_command = &after_args[close + 1..];
+
+    let rest = after_command.trim_start().strip_prefix("if")?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let rest = rest.trim();
+    let rest = COMMENT_TERMINATORS
+        .iter()
+        .find_map(|t| rest.strip_suffix(t))
+        .unwrap_or(rest);
+
+    Some(rest.trim().to_string())
+}
+
+// Parse `s`, keeping only the directives whose guard expression evaluates to `true`
+// against `active` (directives with no guard always pass).
+pub fn parse_with_cfg(
+    s: &str,
+    active: &HashSet<(String, Option<String>)>,
+) -> Result<Vec<Comment>, CfgParseError> {
+    let outcome = parse(s);
+
+    if !outcome.diagnostics.is_empty() {
+        return Err(CfgParseError::Diagnostics(outcome.diagnostics));
+    }
+
+    let mut comments = outcome.comments;
+
+    for comment in comments.iter_mut() {
+        comment.guard = match extract_guard_text(comment) {
+            Some(guard_text) => Some(
+                parse_guard(&guard_text, comment.debug.line, comment.debug.col)
+                    .map_err(CfgParseError::Guard)?,
+            ),
+            None => None,
+        };
+    }
+
+    Ok(comments
+        .into_iter()
+        .filter(|c| c.guard.as_ref().map_or(true, |g| g.eval(active)))
+        .collect())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -164,7 +782,7 @@ mod tests {
     #[test]
     fn test_parse_basic() {
         let input = "/* @cttt.named(123) */\n/* @cttt.change(123,abc) */";
-        insta::assert_yaml_snapshot!(parse(input).unwrap(), @r###"
+        insta::assert_yaml_snapshot!(parse(input).comments, @r###"
         ---
         - command: named
           debug:
@@ -172,29 +790,38 @@ mod tests {
             line: 1
             col: 3
           args:
-            - "123"
+            positional:
+              - "123"
+            named: {}
+          guard: ~
         - command: change
           debug:
             comment: "/* @cttt.change(123,abc) */"
             line: 2
             col: 3
           args:
-            - "123"
-            - abc
+            positional:
+              - "123"
+              - abc
+            named: {}
+          guard: ~
         "###);
     }
 
     #[test]
     fn test_parse_no_command() {
         let input = "// @cttt";
-        insta::assert_yaml_snapshot!(parse(input).unwrap(), @r###"
+        insta::assert_yaml_snapshot!(parse(input).comments, @r###"
         ---
         - command: ~
           debug:
             comment: // @cttt
             line: 1
             col: 3
-          args: []
+          args:
+            positional: []
+            named: {}
+          guard: ~
         "###);
     }
 
@@ -202,7 +829,7 @@ mod tests {
     fn test_parse_nested() {
         let input =
             "// @cttt.named(123)\n// @cttt.named(2)\nx +=1;\n// @cttt.change(3,4,5)\n// @cttt.change(1)";
-        insta::assert_yaml_snapshot!(parse(input).unwrap(), @r###"
+        insta::assert_yaml_snapshot!(parse(input).comments, @r###"
         ---
         - command: named
           debug:
@@ -210,37 +837,49 @@ mod tests {
             line: 1
             col: 3
           args:
-            - "123"
+            positional:
+              - "123"
+            named: {}
+          guard: ~
         - command: named
           debug:
             comment: // @cttt.named(2)
             line: 2
             col: 3
           args:
-            - "2"
+            positional:
+              - "2"
+            named: {}
+          guard: ~
         - command: change
           debug:
             comment: "// @cttt.change(3,4,5)"
             line: 4
             col: 3
           args:
-            - "3"
-            - "4"
-            - "5"
+            positional:
+              - "3"
+              - "4"
+              - "5"
+            named: {}
+          guard: ~
         - command: change
           debug:
             comment: // @cttt.change(1)
             line: 5
             col: 3
           args:
-            - "1"
+            positional:
+              - "1"
+            named: {}
+          guard: ~
         "###);
     }
 
     #[test]
     fn test_parse_case_insensitive() {
         let input = "// @CTTT.named(SPECIAL_BLOCK)\n// @cttt.CHANGE(./foo.txt,abc)";
-        insta::assert_yaml_snapshot!(parse(input).unwrap(), @r###"
+        insta::assert_yaml_snapshot!(parse(input).comments, @r###"
         ---
         - command: named
           debug:
@@ -248,50 +887,62 @@ mod tests {
             line: 1
             col: 0
           args:
-            - SPECIAL_BLOCK
+            positional:
+              - SPECIAL_BLOCK
+            named: {}
+          guard: ~
         - command: CHANGE
           debug:
             comment: "// @cttt.CHANGE(./foo.txt,abc)"
             line: 2
             col: 3
           args:
-            - "./foo.txt"
-            - abc
+            positional:
+              - "./foo.txt"
+              - abc
+            named: {}
+          guard: ~
         "###);
     }
 
     #[test]
     fn test_parse_kebab_command() {
         let input = "// @cttt.named-bar-baz()";
-        insta::assert_yaml_snapshot!(parse(input).unwrap(), @r###"
+        insta::assert_yaml_snapshot!(parse(input).comments, @r###"
         ---
         - command: named-bar-baz
           debug:
             comment: // @cttt.named-bar-baz()
             line: 1
             col: 3
-          args: []
+          args:
+            positional: []
+            named: {}
+          guard: ~
         "###);
     }
 
     #[test]
     fn test_parse_args_whitespace() {
         let input = "// @cttt.change( )";
-        insta::assert_yaml_snapshot!(parse(input).unwrap(), @r###"
+        insta::assert_yaml_snapshot!(parse(input).comments, @r###"
         ---
         - command: change
           debug:
             comment: // @cttt.change( )
             line: 1
             col: 3
-          args: []
+          args:
+            positional: []
+            named: {}
+          guard: ~
         "###);
     }
 
     #[test]
     fn test_parse_args_whitespace_separated() {
         let input = "// @cttt.change(foo, bar)";
-        insta::assert_yaml_snapshot!(parse(input).unwrap(), @r###"
+        insta::assert_yaml_snapshot!(parse(input).comments, @r###"
         ---
         - command: change
           debug:
@@ -299,15 +950,18 @@ mod tests {
             line: 1
             col: 3
           args:
-            - foo
-            - bar
+            positional:
+              - foo
+              - bar
+            named: {}
+          guard: ~
         "###);
     }
 
     #[test]
     fn test_parse_args_whitespace_trailing_comma() {
         let input = "// @cttt.change(foo, bar,)";
-        insta::assert_yaml_snapshot!(parse(input).unwrap(), @r###"
+        insta::assert_yaml_snapshot!(parse(input).comments, @r###"
         ---
         - command: change
           debug:
@@ -315,15 +969,18 @@ mod tests {
             line: 1
             col: 3
           args:
-            - foo
-            - bar
+            positional:
+              - foo
+              - bar
+            named: {}
+          guard: ~
         "###);
     }
 
     #[test]
     fn test_parse_args_characters() {
         let input = "// @cttt.change(./aFoo_Bar-123)";
-        insta::assert_yaml_snapshot!(parse(input).unwrap(), @r###"
+        insta::assert_yaml_snapshot!(parse(input).comments, @r###"
         ---
         - command: change
           debug:
@@ -331,14 +988,17 @@ mod tests {
             line: 1
             col: 3
           args:
-            - "./aFoo_Bar-123"
+            positional:
+              - "./aFoo_Bar-123"
+            named: {}
+          guard: ~
         "###);
     }
 
     #[test]
     fn test_parse_args_file_path() {
         let input = "// @cttt.change(./foo/README.md, /bar/foo.rs)";
-        insta::assert_yaml_snapshot!(parse(input).unwrap(), @r###"
+        insta::assert_yaml_snapshot!(parse(input).comments, @r###"
         ---
         - command: change
           debug:
@@ -346,8 +1006,11 @@ mod tests {
             line: 1
             col: 3
           args:
-            - "./foo/README.md"
-            - /bar/foo.rs
+            positional:
+              - "./foo/README.md"
+              - /bar/foo.rs
+            named: {}
+          guard: ~
         "###);
     }
 
@@ -367,7 +1030,7 @@ mod tests {
             ("<!--", "-->"),
         ] {
             let input = format!("{} {}.{} {}", leading, NAMESPACE, "foo()", trailing);
-            let output = parse(&input).unwrap();
+            let output = parse(&input).comments;
 
             assert_eq!(output[0].command.clone().unwrap(), String::from("foo"));
             assert_eq!(output[0].debug.comment, input.trim_end());
@@ -384,7 +1047,7 @@ mod tests {
             /**
              * @cttt.noop()
              */";
-        insta::assert_yaml_snapshot!(parse(input).unwrap(), @r###"
+        insta::assert_yaml_snapshot!(parse(input).comments, @r###"
         ---
         - command: named
           debug:
@@ -392,20 +1055,39 @@ mod tests {
             line: 3
             col: 15
           args:
-            - "123"
+            positional:
+              - "123"
+            named: {}
+          guard: ~
         - command: noop
           debug:
             comment: "             * @cttt.noop()"
             line: 7
             col: 15
-          args: []
+          args:
+            positional: []
+            named: {}
+          guard: ~
         "###);
     }
 
     #[test]
     fn test_parse_strict_commands() {
         let input = "// @cttt.unknown()\n// @cttt";
-        let commands = vec!["foo".to_string(), "bar".to_string()];
+        let commands = vec![
+            CommandSpec {
+                name: "foo".to_string(),
+                min_positional: 0,
+                max_positional: usize::MAX,
+                allowed_named: vec![],
+            },
+            CommandSpec {
+                name: "bar".to_string(),
+                min_positional: 0,
+                max_positional: usize::MAX,
+                allowed_named: vec![],
+            },
+        ];
 
         let output = parse_strict(input, commands).unwrap_err();
 
@@ -422,4 +1104,310 @@ mod tests {
             _ => panic!("unexpected error"),
         }
     }
+
+    #[test]
+    fn test_parse_strict_named_args() {
+        let input = "// @cttt.change(./foo.rs, line=12, mode=\"strict\")";
+        let commands = vec![CommandSpec {
+            name: "change".to_string(),
+            min_positional: 1,
+            max_positional: 1,
+            allowed_named: vec!["line".to_string(), "mode".to_string()],
+        }];
+
+        let output = parse_strict(input, commands).unwrap();
+
+        assert_eq!(output[0].args.positional, vec!["./foo.rs".to_string()]);
+        assert_eq!(output[0].args.named.get("line").unwrap(), "12");
+        assert_eq!(output[0].args.named.get("mode").unwrap(), "strict");
+    }
+
+    #[test]
+    fn test_parse_strict_named_value_with_comma() {
+        let input = "// @cttt.change(foo, msg=\"hello, world\")";
+        let commands = vec![CommandSpec {
+            name: "change".to_string(),
+            min_positional: 1,
+            max_positional: 1,
+            allowed_named: vec!["msg".to_string()],
+        }];
+
+        let output = parse_strict(input, commands).unwrap();
+
+        assert_eq!(output[0].args.positional, vec!["foo".to_string()]);
+        assert_eq!(output[0].args.named.get("msg").unwrap(), "hello, world");
+    }
+
+    #[test]
+    fn test_parse_strict_unknown_named_key() {
+        let input = "// @cttt.change(./foo.rs, bogus=1)";
+        let commands = vec![CommandSpec {
+            name: "change".to_string(),
+            min_positional: 1,
+            max_positional: 1,
+            allowed_named: vec!["line".to_string()],
+        }];
+
+        match parse_strict(input, commands).unwrap_err() {
+            StrictParseError::InvalidArgs(errors) => {
+                assert_eq!(errors.len(), 1);
+                assert!(errors[0].message.contains("bogus"));
+            }
+            _ => panic!("unexpected error"),
+        }
+    }
+
+    #[test]
+    fn test_parse_strict_missing_required_positional() {
+        let input = "// @cttt.change()";
+        let commands = vec![CommandSpec {
+            name: "change".to_string(),
+            min_positional: 1,
+            max_positional: 1,
+            allowed_named: vec![],
+        }];
+
+        match parse_strict(input, commands).unwrap_err() {
+            StrictParseError::InvalidArgs(errors) => assert_eq!(errors.len(), 1),
+            _ => panic!("unexpected error"),
+        }
+    }
+
+    #[test]
+    fn test_parse_strict_too_many_positionals() {
+        let input = "// @cttt.change(foo, bar)";
+        let commands = vec![CommandSpec {
+            name: "change".to_string(),
+            min_positional: 1,
+            max_positional: 1,
+            allowed_named: vec![],
+        }];
+
+        match parse_strict(input, commands).unwrap_err() {
+            StrictParseError::InvalidArgs(errors) => assert_eq!(errors.len(), 1),
+            _ => panic!("unexpected error"),
+        }
+    }
+
+    #[test]
+    fn test_parse_with_cfg_no_guard_always_passes() {
+        let input = "// @cttt.change(foo)";
+        let active = HashSet::new();
+
+        let output = parse_with_cfg(input, &active).unwrap();
+        assert_eq!(output.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_with_cfg_guard_matches() {
+        let input = "// @cttt.change(foo) if all(any(ci, nightly), not(windows))";
+        let mut active = HashSet::new();
+        active.insert(("nightly".to_string(), None));
+
+        let output = parse_with_cfg(input, &active).unwrap();
+        assert_eq!(output.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_with_cfg_guard_excludes() {
+        let input = "// @cttt.change(foo) if all(any(ci, nightly), not(windows))";
+        let active = HashSet::new();
+
+        let output = parse_with_cfg(input, &active).unwrap();
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn test_parse_with_cfg_key_value() {
+        let input = "// @cttt.change(foo) if os = \"linux\"";
+        let mut active = HashSet::new();
+        active.insert(("os".to_string(), Some("linux".to_string())));
+
+        let output = parse_with_cfg(input, &active).unwrap();
+        assert_eq!(output.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_with_cfg_unknown_predicate_is_false() {
+        let input = "// @cttt.change(foo) if some_unknown_flag";
+        let active = HashSet::new();
+
+        let output = parse_with_cfg(input, &active).unwrap();
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn test_parse_with_cfg_guard_survives_stray_parens_before_command() {
+        let input = "// change (something) @cttt.change(foo) if ci";
+        let active = HashSet::new();
+
+        let output = parse_with_cfg(input, &active).unwrap();
+        assert!(output.is_empty());
+
+        let mut active = HashSet::new();
+        active.insert(("ci".to_string(), None));
+        let output = parse_with_cfg(input, &active).unwrap();
+        assert_eq!(output.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_with_cfg_unbalanced_parens_is_parse_error() {
+        let input = "// @cttt.change(foo) if all(ci, nightly";
+        let active = HashSet::new();
+
+        match parse_with_cfg(input, &active).unwrap_err() {
+            CfgParseError::Guard(e) => assert_eq!(e.line, 1),
+            _ => panic!("expected a guard parse error"),
+        }
+    }
+
+    #[test]
+    fn test_parse_with_cfg_trailing_comma_is_parse_error() {
+        let input = "// @cttt.change(foo) if all(ci, nightly,)";
+        let active = HashSet::new();
+
+        match parse_with_cfg(input, &active).unwrap_err() {
+            CfgParseError::Guard(_) => (),
+            _ => panic!("expected a guard parse error"),
+        }
+    }
+
+    #[test]
+    fn test_parse_recovers_from_malformed_directive() {
+        let input = "// @cttt.named(123)\n// @cttt.broken(\nx += 1;\n// @cttt.change(456)";
+        let outcome = parse(input);
+
+        assert_eq!(outcome.comments.len(), 2);
+        assert_eq!(outcome.comments[0].command, Some("named".to_string()));
+        assert_eq!(outcome.comments[1].command, Some("change".to_string()));
+
+        assert_eq!(outcome.diagnostics.len(), 1);
+        assert_eq!(outcome.diagnostics[0].line, 2);
+    }
+
+    #[test]
+    fn test_parse_ignores_unrelated_parse_failures() {
+        // A line that merely resembles code, with no `@cttt` marker, never produces
+        // a diagnostic even if pest can't make sense of it.
+        let outcome = parse(")))");
+        assert!(outcome.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_parse_with_options_lowercase_normalizes_command() {
+        let input = "// @cttt.CHANGE(foo)";
+        let options = ParseOptions {
+            command_case: CommandCase::Lowercase,
+            ..ParseOptions::default()
+        };
+
+        let outcome = parse_with_options(input, &options);
+        assert_eq!(outcome.comments[0].command, Some("change".to_string()));
+    }
+
+    #[test]
+    fn test_parse_with_options_kebab_normalizes_command() {
+        let input = "// @cttt.Named_Block()";
+        let options = ParseOptions {
+            command_case: CommandCase::Kebab,
+            ..ParseOptions::default()
+        };
+
+        let outcome = parse_with_options(input, &options);
+        assert_eq!(
+            outcome.comments[0].command,
+            Some("named-block".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_strict_with_options_case_insensitive_match() {
+        let input = "// @cttt.CHANGE(foo)\n// @cttt.change(bar)\n// @cttt.Change(baz)";
+        let commands = vec![CommandSpec {
+            name: "change".to_string(),
+            min_positional: 1,
+            max_positional: 1,
+            allowed_named: vec![],
+        }];
+        let options = ParseOptions {
+            command_case: CommandCase::Lowercase,
+            ..ParseOptions::default()
+        };
+
+        let output = parse_strict_with_options(input, commands, &options).unwrap();
+        assert_eq!(output.len(), 3);
+        assert!(output.iter().all(|c| c.command == Some("change".to_string())));
+    }
+
+    #[test]
+    fn test_parse_with_options_custom_namespace() {
+        let input = "// @mytool.change(foo)";
+        let options = ParseOptions {
+            namespace: "@mytool".to_string(),
+            ..ParseOptions::default()
+        };
+
+        let outcome = parse_with_options(input, &options);
+        assert_eq!(outcome.comments[0].command, Some("change".to_string()));
+    }
+
+    #[test]
+    fn test_parse_with_options_custom_namespace_rejects_default() {
+        let input = "// @cttt.change(foo)";
+        let options = ParseOptions {
+            namespace: "@mytool".to_string(),
+            ..ParseOptions::default()
+        };
+
+        let outcome = parse_with_options(input, &options);
+        assert!(outcome.comments.is_empty());
+        assert!(outcome.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_parse_with_options_custom_namespace_multiple_per_line() {
+        let input = "/* @mytool.a(1) */ /* @mytool.b(2) */";
+        let options = ParseOptions {
+            namespace: "@mytool".to_string(),
+            ..ParseOptions::default()
+        };
+
+        let outcome = parse_with_options(input, &options);
+        assert!(outcome.diagnostics.is_empty());
+        assert_eq!(outcome.comments.len(), 2);
+        assert_eq!(outcome.comments[0].command, Some("a".to_string()));
+        assert_eq!(outcome.comments[1].command, Some("b".to_string()));
+    }
+
+    #[test]
+    fn test_parse_with_options_custom_namespace_diagnostic_col() {
+        let default_input = "// @cttt.change(";
+        let default_col = parse(default_input).diagnostics[0].col;
+
+        let custom_input = "// @mytool.change(";
+        let options = ParseOptions {
+            namespace: "@mytool".to_string(),
+            ..ParseOptions::default()
+        };
+        let custom_col = parse_with_options(custom_input, &options).diagnostics[0].col;
+
+        assert_eq!(custom_col, default_col);
+    }
+
+    #[test]
+    fn test_parse_with_options_custom_namespace_repeated_in_comment_text() {
+        let input = "// about @mytool stuff, @mytool.change(foo)";
+        let options = ParseOptions {
+            namespace: "@mytool".to_string(),
+            ..ParseOptions::default()
+        };
+
+        let outcome = parse_with_options(input, &options);
+        assert_eq!(outcome.comments.len(), 1);
+
+        let comment = &outcome.comments[0];
+        assert_eq!(comment.command, Some("change".to_string()));
+        assert_eq!(comment.debug.comment, input);
+        assert_eq!(comment.args.positional, vec!["foo".to_string()]);
+    }
 }